@@ -0,0 +1,218 @@
+use ndarray::{Array3, ArrayView3, ArrayViewMut3, ShapeBuilder};
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::mem;
+use std::ptr::NonNull;
+use std::slice;
+
+/// Default alignment (bytes) for the stencil working arrays: wide enough
+/// for AVX loads. Set `config.align_bytes` to 64 for AVX-512 targets instead.
+pub const DEFAULT_ALIGN: usize = 32;
+
+fn elems_per_chunk(align: usize) -> usize {
+    align / mem::size_of::<f64>()
+}
+
+/// Rounds `len` elements up to a whole number of `align`-byte chunks.
+fn pad_to_align(len: usize, align: usize) -> usize {
+    let per_chunk = elems_per_chunk(align);
+    let rem = len % per_chunk;
+    if rem == 0 { len } else { len + (per_chunk - rem) }
+}
+
+/// Owning, `align`-byte aligned backing store for one of the stencil
+/// arrays (`Potentials.{a,b}`, the `phi` buffer, `wfnc_energy`/`evolve`'s
+/// temporary `work` arrays).
+///
+/// Allocates and frees through the raw global allocator with its own
+/// stored `Layout`, since a `Vec<f64>` always assumes `align_of::<f64>() ==
+/// 8` and would free a custom-aligned allocation with the wrong layout.
+///
+/// The z-axis is padded up to a whole number of alignment chunks so every
+/// `(i, j, 0)` row starts aligned; `view`/`view_mut` hide that padding
+/// behind ordinary strides, so callers never see it.
+pub struct AlignedArray3 {
+    ptr: NonNull<f64>,
+    layout: Layout,
+    len: usize,
+    dims: (usize, usize, usize),
+    padded_z: usize,
+    align: usize,
+}
+
+unsafe impl Send for AlignedArray3 {}
+unsafe impl Sync for AlignedArray3 {}
+
+impl AlignedArray3 {
+    /// # Panics
+    /// * If `align` isn't a power of two, or is smaller than `align_of::<f64>()`.
+    pub fn zeros(dims: (usize, usize, usize), align: usize) -> AlignedArray3 {
+        assert!(align.is_power_of_two() && align >= mem::align_of::<f64>(),
+                "alignment must be a power of two of at least {} bytes, got {}",
+                mem::align_of::<f64>(),
+                align);
+        let padded_z = pad_to_align(dims.2, align);
+        let len = dims.0 * dims.1 * padded_z;
+        let layout = Layout::from_size_align(len * mem::size_of::<f64>(), align)
+            .expect("aligned array size overflows isize");
+        // `GlobalAlloc::alloc`/`dealloc` are UB on a zero-size layout, so a
+        // degenerate dimension (any axis 0) gets a dangling pointer instead
+        // of going anywhere near the allocator -- `Drop` checks `layout`'s
+        // size before freeing to match.
+        let ptr = if len == 0 {
+            NonNull::dangling()
+        } else {
+            let raw = unsafe { alloc::alloc_zeroed(layout) } as *mut f64;
+            match NonNull::new(raw) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            }
+        };
+        AlignedArray3 {
+            ptr: ptr,
+            layout: layout,
+            len: len,
+            dims: dims,
+            padded_z: padded_z,
+            align: align,
+        }
+    }
+
+    /// Copies `source` into a freshly aligned buffer of the same shape --
+    /// used once, at the start of a solve, to bring externally sourced
+    /// arrays (initial conditions, CSV-loaded wavefunctions) into aligned
+    /// storage so every subsequent in-place `par_apply` stays aligned.
+    pub fn from_array3(source: &Array3<f64>, align: usize) -> AlignedArray3 {
+        let mut aligned = AlignedArray3::zeros(source.dim(), align);
+        aligned.view_mut().assign(source);
+        aligned
+    }
+
+    pub fn dim(&self) -> (usize, usize, usize) {
+        self.dims
+    }
+
+    fn as_slice(&self) -> &[f64] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [f64] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Logical-shape read view. The z-axis stride skips the tail padding
+    /// on each row, so this reads exactly `dims` worth of data despite the
+    /// larger backing allocation.
+    pub fn view(&self) -> ArrayView3<f64> {
+        if self.len == 0 {
+            // `from_shape` rejects a non-default stride paired with a zero
+            // outer (x or y) axis as out-of-bounds even though there's no
+            // data to index either way, so a degenerate buffer falls back
+            // to the dims' own default strides instead of the padded ones.
+            return ArrayView3::from_shape(self.dims, self.as_slice())
+                .expect("degenerate aligned buffer shape mismatch");
+        }
+        let strides = (self.dims.1 * self.padded_z, self.padded_z, 1);
+        ArrayView3::from_shape(self.dims.strides(strides), self.as_slice())
+            .expect("aligned buffer shape/stride mismatch")
+    }
+
+    /// Mutable counterpart of `view`.
+    pub fn view_mut(&mut self) -> ArrayViewMut3<f64> {
+        let dims = self.dims;
+        if self.len == 0 {
+            return ArrayViewMut3::from_shape(dims, self.as_mut_slice())
+                .expect("degenerate aligned buffer shape mismatch");
+        }
+        let strides = (dims.1 * self.padded_z, self.padded_z, 1);
+        ArrayViewMut3::from_shape(dims.strides(strides), self.as_mut_slice())
+            .expect("aligned buffer shape/stride mismatch")
+    }
+}
+
+impl Clone for AlignedArray3 {
+    fn clone(&self) -> AlignedArray3 {
+        let mut out = AlignedArray3::zeros(self.dims, self.align);
+        out.view_mut().assign(&self.view());
+        out
+    }
+}
+
+impl Drop for AlignedArray3 {
+    fn drop(&mut self) {
+        if self.layout.size() > 0 {
+            unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, self.layout) };
+        }
+    }
+}
+
+impl fmt::Debug for AlignedArray3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlignedArray3")
+            .field("dims", &self.dims)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zeros_is_actually_zero_filled_and_the_right_shape() {
+        let a = AlignedArray3::zeros((2, 3, 4), 32);
+        assert_eq!(a.dim(), (2, 3, 4));
+        assert!(a.view().iter().all(|&x| x == 0.));
+    }
+
+    #[test]
+    fn zeros_rejects_an_alignment_smaller_than_f64_or_not_a_power_of_two() {
+        assert!(::std::panic::catch_unwind(|| AlignedArray3::zeros((2, 2, 2), 4)).is_err());
+        assert!(::std::panic::catch_unwind(|| AlignedArray3::zeros((2, 2, 2), 24)).is_err());
+    }
+
+    #[test]
+    fn zero_sized_dims_never_touch_the_allocator() {
+        // A degenerate (zero-length) axis drives `len` to zero, so `zeros`
+        // must hand back a dangling pointer instead of an alloc_zeroed call
+        // with a zero-size Layout -- and Drop must not try to dealloc it.
+        let a = AlignedArray3::zeros((0, 3, 4), 32);
+        assert_eq!(a.dim(), (0, 3, 4));
+        assert_eq!(a.view().iter().count(), 0);
+        drop(a);
+    }
+
+    #[test]
+    fn from_array3_round_trips_values_through_view() {
+        let source = Array3::from_shape_fn((3, 5, 7), |(i, j, k)| (i * 100 + j * 10 + k) as f64);
+        let aligned = AlignedArray3::from_array3(&source, 64);
+        assert_eq!(aligned.dim(), source.dim());
+        assert_eq!(aligned.view(), source.view());
+    }
+
+    #[test]
+    fn view_mut_writes_are_visible_through_view() {
+        let mut a = AlignedArray3::zeros((2, 2, 2), 32);
+        a.view_mut()[[1, 0, 1]] = 42.;
+        assert_eq!(a.view()[[1, 0, 1]], 42.);
+    }
+
+    #[test]
+    fn clone_is_an_independent_deep_copy() {
+        let mut a = AlignedArray3::zeros((2, 2, 2), 32);
+        a.view_mut()[[0, 1, 1]] = 7.;
+        let b = a.clone();
+        a.view_mut()[[0, 1, 1]] = 9.;
+        assert_eq!(b.view()[[0, 1, 1]], 7.);
+        assert_eq!(a.view()[[0, 1, 1]], 9.);
+    }
+
+    #[test]
+    fn view_hides_the_z_axis_padding() {
+        // align=32 is 4 f64s per chunk, so a z-length of 5 pads to 8 in the
+        // backing store -- `view`'s strides must still report length 5.
+        let a = AlignedArray3::zeros((1, 1, 5), 32);
+        assert_eq!(a.view().dim(), (1, 1, 5));
+    }
+}
@@ -1,17 +1,46 @@
 use ndarray::{Array3, ArrayView3, ArrayViewMut3, Zip};
+#[cfg(not(target_arch = "wasm32"))]
 use ndarray_parallel::prelude::*;
 use slog::Logger;
 use std::f64::MAX;
+use std::sync::Arc;
+use std::thread;
 use config;
-use config::{Config, Grid, Index3, PotentialType};
+use config::{Config, Index3, PotentialType};
 use potential;
 use output;
+use align::AlignedArray3;
+use stats;
+use transport::{Transport, SingleRank, ThreadRanks, HALO_WIDTH};
+
+/// `zip.par_apply(f)` where there's a rayon thread pool to hand it to;
+/// `zip.apply(f)` -- ndarray's own sequential applicator -- on
+/// `wasm32-unknown-unknown`, which has none.
+#[cfg(not(target_arch = "wasm32"))]
+macro_rules! par_apply {
+    ($zip:expr, $f:expr) => {
+        $zip.par_apply($f)
+    };
+}
+#[cfg(target_arch = "wasm32")]
+macro_rules! par_apply {
+    ($zip:expr, $f:expr) => {
+        $zip.apply($f)
+    };
+}
+
+/// Number of trailing converged-window snapshots of `norm_energy` kept per
+/// state, used as the sample set for `stats::estimate`'s confidence interval.
+/// The interval width itself comes from `config.output.confidence`, and the
+/// summaries are printed via `output::excitation_summary` -- both `config`
+/// and `output` are part of the wider crate this snapshot doesn't include.
+const ENERGY_SAMPLE_WINDOW: usize = 10;
 
 #[derive(Debug)]
 pub struct Potentials {
-    pub v: Array3<f64>,
-    a: Array3<f64>,
-    b: Array3<f64>,
+    pub v: AlignedArray3,
+    a: AlignedArray3,
+    b: AlignedArray3,
     epsilon: f64,
 }
 
@@ -19,7 +48,7 @@ pub struct Potentials {
 #[derive(Debug)]
 struct Params<'a, 'b> {
     potentials: &'b Potentials,
-    phi: &'a mut Array3<f64>,
+    phi: &'a mut AlignedArray3,
 }
 
 #[derive(Debug)]
@@ -45,8 +74,12 @@ fn load_potential_arrays(config: &Config, log: &Logger) -> Potentials {
     };
 
 
-    let b = 1. / (1. + config.grid.dt * &v / 2.);
-    let a = (1. - config.grid.dt * &v / 2.) * &b;
+    let mut b = AlignedArray3::zeros(v.dim(), config.align_bytes);
+    par_apply!(Zip::from(b.view_mut()).and(&v),
+               |b, &v| *b = 1. / (1. + config.grid.dt * v / 2.));
+    let mut a = AlignedArray3::zeros(v.dim(), config.align_bytes);
+    par_apply!(Zip::from(a.view_mut()).and(&v).and(b.view()),
+               |a, &v, &b| *a = (1. - config.grid.dt * v / 2.) * b);
 
     // We can't do this in a par.
     // AFAIK, this is the safest way to work with the float here.
@@ -70,22 +103,45 @@ fn load_potential_arrays(config: &Config, log: &Logger) -> Potentials {
     }
 
     Potentials {
-        v: v,
+        v: AlignedArray3::from_array3(&v, config.align_bytes),
         a: a,
         b: b,
         epsilon: epsilon,
     }
 }
 
-/// Runs the calculation and holds long term (system time) wavefunction storage
-pub fn run(config: &Config, log: &Logger) {
+/// Runs the calculation and holds long term (system time) wavefunction storage.
+///
+/// Still sizes `phi`/`Potentials` to the whole grid rather than a per-rank
+/// z-slab; use `run_distributed` for an actual multi-rank solve.
+///
+/// # Panics
+/// * If `transport.num_ranks() > 1`: every rank would allreduce identical
+///   full-grid observables instead of real partial sums.
+pub fn run(config: &Config, log: &Logger, transport: &Transport) {
+    assert_eq!(transport.num_ranks(),
+               1,
+               "run sizes phi/Potentials to the whole grid, so driving it with more than one \
+                rank would allreduce {} identical copies of every observable instead of real \
+                partial sums -- use run_distributed for an actual per-rank z-slab solve",
+               transport.num_ranks());
+    info!(log,
+          "Running with {} rank(s) configured, this is rank {}",
+          transport.num_ranks(),
+          transport.rank());
     let potentials = load_potential_arrays(config, log);
+    let initial = AlignedArray3::from_array3(&config::set_initial_conditions(config, log),
+                                              config.align_bytes);
 
-    let mut w_store: Vec<Array3<f64>> = Vec::new();
+    let mut w_store: Vec<AlignedArray3> = Vec::new();
+    let mut energy_store: Vec<Vec<f64>> = Vec::new();
     for wnum in config.wavenum..config.wavemax + 1 {
         //TODO: This error probably isn't the best way of handling this situation.
-        match solve(config, log, &potentials, wnum, &w_store) {
-            Some(w) => w_store.push(w),
+        match solve(config, log, &potentials, wnum, &w_store, transport, 0, &initial) {
+            Some((w, energy_samples)) => {
+                w_store.push(w);
+                energy_store.push(energy_samples);
+            }
             None => {
                 panic!("Wavefunction is not converged. Cannot continue until convergence is \
                         reached.")
@@ -93,56 +149,338 @@ pub fn run(config: &Config, log: &Logger) {
         }
         //reInitSolver()
     }
+    // `energy_store[0]` is only the ground state when this run actually
+    // started from wavenum 0 -- if we resumed from a nonzero `config.wavenum`
+    // (via `load_wavefunctions`), it's the first newly *computed* excited
+    // state instead, and there is no ground-state estimate to measure
+    // against here at all.
+    if config.wavenum == 0 {
+        if let Some(ground) = energy_store.first() {
+            if ground.len() > 1 {
+                let ground_estimate = stats::estimate(ground, config.output.confidence);
+                for (idx, samples) in energy_store.iter().enumerate().skip(1) {
+                    if samples.len() > 1 {
+                        let excited_estimate = stats::estimate(samples, config.output.confidence);
+                        let excitation = stats::excitation_energy(&excited_estimate, &ground_estimate);
+                        output::excitation_summary(config.wavenum + idx as u8, &excitation);
+                    }
+                }
+            }
+        }
+    } else {
+        warn!(log,
+              "Resumed from wavenum {}; ground state wasn't computed this run, so no \
+               excitation energies can be reported",
+              config.wavenum);
+    }
     // done with main calculation.
     // solve finalise
 }
 
+/// Divides `total` work-area z-cells into `num_ranks` contiguous chunks as
+/// evenly as possible (the first `total % num_ranks` ranks take one extra
+/// cell), returning each rank's `(work_z_start, work_z_len)`.
+fn z_partition(total: usize, num_ranks: usize) -> Vec<(usize, usize)> {
+    let base = total / num_ranks;
+    let extra = total % num_ranks;
+    let mut start = 0;
+    (0..num_ranks)
+        .map(|rank| {
+            let len = base + if rank < extra { 1 } else { 0 };
+            let slab = (start, len);
+            start += len;
+            slab
+        })
+        .collect()
+}
+
+/// Carves `[z_lo, z_hi)` (in `full`'s own padded z-index space, halo
+/// included) out of a whole-grid aligned buffer into a freshly aligned
+/// slab -- how `run_distributed` splits `load_potential_arrays`/
+/// `config::set_initial_conditions`'s whole-grid output per rank.
+fn slab_slice(full: &AlignedArray3, z_lo: usize, z_hi: usize, align: usize) -> AlignedArray3 {
+    let owned = full.view().slice(s![.., .., z_lo as isize..z_hi as isize]).to_owned();
+    AlignedArray3::from_array3(&owned, align)
+}
+
+/// `slab_slice`, applied to every buffer `Potentials` holds.
+fn slab_potentials(full: &Potentials, z_lo: usize, z_hi: usize, align: usize) -> Potentials {
+    Potentials {
+        v: slab_slice(&full.v, z_lo, z_hi, align),
+        a: slab_slice(&full.a, z_lo, z_hi, align),
+        b: slab_slice(&full.b, z_lo, z_hi, align),
+        epsilon: full.epsilon,
+    }
+}
+
+/// `run`, but with `config.grid.size`'s z-axis decomposed into `num_ranks`
+/// contiguous slabs, one `std::thread` worker per rank exchanging halos via
+/// `transport::ThreadRanks`. `num_ranks <= 1` just delegates to `run`. Only
+/// rank 0's `solve` call prints progress or saves wavefunctions to disk.
+///
+/// # Panics
+/// * If any worker thread panics (e.g. on non-convergence), the same as
+///   `run` panicking on `None` from `solve`.
+pub fn run_distributed(config: Config, log: Logger, num_ranks: usize) {
+    if num_ranks <= 1 {
+        run(&config, &log, &SingleRank);
+        return;
+    }
+    assert!(num_ranks <= config.grid.size.z as usize,
+            "{} ranks can't each own a non-empty z-slab of a {}-cell grid",
+            num_ranks,
+            config.grid.size.z);
+
+    let potentials = load_potential_arrays(&config, &log);
+    let initial = AlignedArray3::from_array3(&config::set_initial_conditions(&config, &log),
+                                              config.align_bytes);
+    let ranges = z_partition(config.grid.size.z as usize, num_ranks);
+    let align = config.align_bytes;
+
+    let config = Arc::new(config);
+    let log = Arc::new(log);
+
+    let handles: Vec<_> = ThreadRanks::ring(num_ranks)
+        .into_iter()
+        .zip(ranges.into_iter())
+        .map(|(rank_transport, (z_start, z_len))| {
+            let config = Arc::clone(&config);
+            let log = Arc::clone(&log);
+            let z_hi = z_start + z_len + 2 * HALO_WIDTH;
+            let slab_pots = slab_potentials(&potentials, z_start, z_hi, align);
+            let slab_initial = slab_slice(&initial, z_start, z_hi, align);
+            thread::spawn(move || {
+                let mut w_store: Vec<AlignedArray3> = Vec::new();
+                for wnum in config.wavenum..config.wavemax + 1 {
+                    match solve(&config,
+                                &log,
+                                &slab_pots,
+                                wnum,
+                                &w_store,
+                                &rank_transport,
+                                z_start,
+                                &slab_initial) {
+                        Some((w, _energy_samples)) => w_store.push(w),
+                        None => {
+                            panic!("Wavefunction is not converged. Cannot continue until \
+                                    convergence is reached.")
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().expect("distributed worker thread panicked");
+    }
+}
+
+/// `energies` holds one eigen-energy per state solved in the call;
+/// `wavefunctions` holds every flattened wavefunction in the store, i.e. any
+/// supplied overrides first, then the newly solved states. This is the
+/// shape `wasm::run` hands back to its JS caller in place of the files
+/// `run` would otherwise write to `./input`/disk.
+#[derive(Debug, Serialize)]
+pub struct SolveResult {
+    pub energies: Vec<f64>,
+    pub wavefunctions: Vec<Vec<f64>>,
+}
+
+/// `run`, but for hosts with no filesystem (i.e. `wasm32-unknown-unknown`):
+/// potentials/wavefunctions come in as in-memory buffers instead of
+/// `./input/*.csv`, and results go back as a value. `solve` still prints
+/// progress through `output` and, when `config.output.save_wavefns` is set,
+/// still writes each wavefunction to disk; with no `potential` buffer the
+/// potential comes from `load_potential_arrays`, as in `run`.
+/// The `par_apply!` call sites fall back to `Zip::apply` on that target,
+/// since it has no rayon thread pool to hand work off to.
+///
+/// Unlike `run`, a bad `potential`/`wavefunctions` override here is caller
+/// input arriving over the wasm boundary rather than a local programming
+/// error, so a shape mismatch -- or too few previously converged
+/// wavefunctions to resume from `config.wavenum` -- comes back as an `Err`
+/// the JS caller can catch instead of trapping the whole wasm instance.
+/// `transport.num_ranks() > 1` is refused the same way, and for the same
+/// reason as `run`'s panic: this sizes `phi`/`Potentials` to the whole
+/// grid, so every rank would allreduce identical copies of each
+/// observable. `run_distributed`'s `std::thread` workers aren't an option
+/// here anyway -- `wasm32-unknown-unknown` has no OS threads to spawn.
+#[cfg(feature = "wasm")]
+pub fn run_in_memory(config: &Config,
+                      log: &Logger,
+                      transport: &Transport,
+                      potential: Option<Vec<f64>>,
+                      wavefunctions: Option<Vec<Vec<f64>>>)
+                      -> Result<SolveResult, String> {
+    if transport.num_ranks() != 1 {
+        return Err(format!("run_in_memory sizes phi/Potentials to the whole grid, so driving it \
+                             with {} ranks would allreduce identical copies of every observable \
+                             instead of real partial sums",
+                            transport.num_ranks()));
+    }
+    let potentials = match potential {
+        Some(flat) => potentials_from_flat(config, flat)?,
+        None => load_potential_arrays(config, log),
+    };
+    let initial = AlignedArray3::from_array3(&config::set_initial_conditions(config, log),
+                                              config.align_bytes);
+
+    let mut w_store: Vec<AlignedArray3> = Vec::new();
+    if let Some(overrides) = wavefunctions {
+        let dims = padded_grid_dims(config);
+        for flat in overrides {
+            let plain = shape_from_flat(dims, flat, "wavefunction override")?;
+            w_store.push(AlignedArray3::from_array3(&plain, config.align_bytes));
+        }
+    }
+    if w_store.len() < config.wavenum as usize {
+        return Err(format!("resuming from wavenum {} needs {} previously converged \
+                             wavefunction(s), only {} were supplied",
+                            config.wavenum,
+                            config.wavenum,
+                            w_store.len()));
+    }
+
+    let mut energies = Vec::new();
+    for wnum in config.wavenum..config.wavemax + 1 {
+        match solve(config, log, &potentials, wnum, &w_store, transport, 0, &initial) {
+            Some((w, energy_samples)) => {
+                energies.push(*energy_samples.last()
+                                   .expect("a converged state recorded at least one energy sample"));
+                w_store.push(w);
+            }
+            None => {
+                return Err("Wavefunction is not converged. Cannot continue until convergence \
+                            is reached.".to_string())
+            }
+        }
+    }
+
+    Ok(SolveResult {
+        energies: energies,
+        wavefunctions: w_store.into_iter().map(|w| w.view().to_owned().into_raw_vec()).collect(),
+    })
+}
+
+/// Full (ghost-padded) grid dimensions for `config`, the shape every
+/// wavefunction/potential buffer -- in memory or on disk -- must match.
+#[cfg(feature = "wasm")]
+fn padded_grid_dims(config: &Config) -> (usize, usize, usize) {
+    let num = &config.grid.size;
+    ((num.x + 6) as usize, (num.y + 6) as usize, (num.z + 6) as usize)
+}
+
+/// Shared `Array3::from_shape_vec` error conversion for the two in-memory
+/// override sites below, so `what` is the only piece that varies between
+/// the wavefunction and potential error messages handed back to JS.
+#[cfg(feature = "wasm")]
+fn shape_from_flat(dims: (usize, usize, usize), flat: Vec<f64>, what: &str) -> Result<Array3<f64>, String> {
+    Array3::from_shape_vec(dims, flat).map_err(|err| format!("{} does not match the configured \
+                                                                grid size: {}", what, err))
+}
+
+/// Builds `Potentials` directly from a flattened in-memory buffer instead
+/// of going through `potential::from_file`/`from_script`, mirroring what
+/// `load_potential_arrays` does once it has `v` in hand.
+#[cfg(feature = "wasm")]
+fn potentials_from_flat(config: &Config, flat: Vec<f64>) -> Result<Potentials, String> {
+    let dims = padded_grid_dims(config);
+    let v = shape_from_flat(dims, flat, "potential override")?;
+
+    let mut b = AlignedArray3::zeros(v.dim(), config.align_bytes);
+    par_apply!(Zip::from(b.view_mut()).and(&v),
+               |b, &v| *b = 1. / (1. + config.grid.dt * v / 2.));
+    let mut a = AlignedArray3::zeros(v.dim(), config.align_bytes);
+    par_apply!(Zip::from(a.view_mut()).and(&v).and(b.view()),
+               |a, &v, &b| *a = (1. - config.grid.dt * v / 2.) * b);
+
+    let mut minima: f64 = MAX;
+    for el in v.iter() {
+        if el.is_finite() {
+            minima = minima.min(*el);
+        }
+    }
+    let epsilon = 2. * minima.abs();
+
+    Ok(Potentials {
+        v: AlignedArray3::from_array3(&v, config.align_bytes),
+        a: a,
+        b: b,
+        epsilon: epsilon,
+    })
+}
+
 /// Runs the actual computation once system is setup and ready.
+///
+/// `z_offset` is this rank's first global work-area z-index (0 for the
+/// whole-grid callers), used to map a sliced-in `initial`/`w_store` back to
+/// the right point in `config`'s global potential/grid.
 fn solve(config: &Config,
          log: &Logger,
          pots: &Potentials,
          wnum: u8,
-         w_store: &Vec<Array3<f64>>)
-         -> Option<Array3<f64>> {
+         w_store: &Vec<AlignedArray3>,
+         transport: &Transport,
+         z_offset: usize,
+         initial: &AlignedArray3)
+         -> Option<(AlignedArray3, Vec<f64>)> {
 
     // Initial conditions from config file if ground state,
     // but start from previously converged wfn if we're an excited state.
     // NOTE: This may not alwans be the sane choice. If we have a converged
     // low resolution version on file we'll want that instead
+    let mut seed = if wnum > 0 {
+        w_store[wnum as usize - 1].clone()
+    } else {
+        initial.clone()
+    };
     let mut params = Params {
         potentials: pots,
-        phi: &mut if wnum > 0 {
-                      w_store[wnum as usize - 1].clone()
-                  } else {
-                      config::set_initial_conditions(config, log)
-                  },
+        phi: &mut seed,
     };
 
-    output::print_observable_header(wnum);
+    // Every rank solves the same wnum independently, so only rank 0 prints
+    // progress/writes to disk -- otherwise every rank would race to report
+    // or save the same state.
+    let verbose = transport.rank() == 0;
+    if verbose {
+        output::print_observable_header(wnum);
+    }
 
     let mut step = 0;
     let mut done = false;
     let mut converged = false;
     let mut last_energy = MAX; //std::f64::MAX
     let mut display_energy = MAX;
+    // Trailing window of per-snapshot norm_energy values, used by the
+    // excitation-energy report's confidence interval once we converge.
+    let mut energy_samples: Vec<f64> = Vec::with_capacity(ENERGY_SAMPLE_WINDOW);
     while !done {
 
-        let observables = compute_observables(config, &params);
+        let observables = compute_observables(config, &params, transport, z_offset);
         let norm_energy = observables.energy / observables.norm2;
         // Orthoganalise wavefunction
         if wnum > 0 {
             normalise_wavefunction(params.phi, observables.norm2);
-            orthogonalise_wavefunction(wnum, params.phi, w_store);
+            orthogonalise_wavefunction(wnum, params.phi, w_store, transport);
         }
         //NOTE: Need to do a floating point comparison here if we want steps to be more than 2^64 (~1e19)
         // But I think it's just best to not have this option. 1e19 max.
         if step % config.output.snap_update == 0 {
             //TODO: I think we can do away with SNAPUPDATE now. Kill this if.
-            config::symmetrise_wavefunction(config, params.phi);
+            config::symmetrise_wavefunction(config, params.phi.view_mut());
             normalise_wavefunction(params.phi, observables.norm2);
 
+            energy_samples.push(norm_energy);
+            if energy_samples.len() > ENERGY_SAMPLE_WINDOW {
+                energy_samples.remove(0);
+            }
+
             if (norm_energy - last_energy).abs() < config.tolerance {
-                output::summary(&observables, wnum, config.grid.size.x as f64);
+                if verbose {
+                    output::summary(&observables, wnum, config.grid.size.x as f64);
+                }
                 converged = true;
                 break;
             } else {
@@ -152,18 +490,25 @@ fn solve(config: &Config,
         }
         let tau = (step as f64) * config.grid.dt;
         let diff = (display_energy - norm_energy).abs();
-        output::measurements(tau, diff, &observables);
+        if verbose {
+            output::measurements(tau, diff, &observables);
+        }
         if step < config.max_steps {
-            evolve(wnum, config, &mut params, w_store);
+            evolve(wnum, config, &mut params, w_store, transport);
         }
         step += config.output.screen_update;
         done = step > config.max_steps;
     }
 
-    if config.output.save_wavefns {
+    if verbose && config.output.save_wavefns {
         //NOTE: This wil save regardless of whether it is converged or not, so we flag it if that's the case.
         info!(log, "Saving wavefunction {} to disk", wnum);
-        match output::wavefunction_plain(&params.phi, wnum, converged) {
+        // params.phi is an align::AlignedArray3, not the &Array3<f64> the
+        // baseline output::wavefunction_plain took -- that signature needs
+        // updating to accept an ArrayView3<f64> in lockstep with this call
+        // site. output.rs isn't part of this snapshot, so that change can't
+        // be made here.
+        match output::wavefunction_plain(params.phi.view(), wnum, converged) {
             Ok(_) => {}
             Err(err) => crit!(log, "Could not write wavefunction to disk: {}", err),
         }
@@ -171,20 +516,21 @@ fn solve(config: &Config,
 
     if converged {
         info!(log, "Caluculation Converged");
-        Some(params.phi.clone())
+        Some((params.phi.clone(), energy_samples))
     } else {
         warn!(log, "Caluculation stopped due to maximum step limit.");
         None
     }
 }
 
-/// Computes observable values of the system, for example the energy
-fn compute_observables(config: &Config, params: &Params) -> Observables {
-    let energy = wfnc_energy(config, params);
-    let work = get_work_area(params.phi);
-    let norm2 = get_norm_squared(&work);
-    let v_infinity = get_v_infinity_expectation_value(&work, config);
-    let r2 = get_r_squared_expectation_value(&work, &config.grid);
+/// Computes observable values of the system, for example the energy.
+/// `z_offset` is this rank's global work-area z-offset (see `solve`'s doc).
+fn compute_observables(config: &Config, params: &Params, transport: &Transport, z_offset: usize) -> Observables {
+    let energy = wfnc_energy(config, params, transport);
+    let work = get_work_area(params.phi.view());
+    let norm2 = get_norm_squared(&work, transport);
+    let v_infinity = get_v_infinity_expectation_value(&work, config, transport, z_offset);
+    let r2 = get_r_squared_expectation_value(&work, config, transport, z_offset);
 
     Observables {
         energy: energy,
@@ -195,126 +541,146 @@ fn compute_observables(config: &Config, params: &Params) -> Observables {
 }
 
 /// Normalisation of wavefunction
-fn get_norm_squared(w: &ArrayView3<f64>) -> f64 {
+fn get_norm_squared(w: &ArrayView3<f64>, transport: &Transport) -> f64 {
     //NOTE: No complex conjugation due to all real input for now
-    w.into_par_iter().map(|&el| el * el).sum()
+    #[cfg(not(target_arch = "wasm32"))]
+    let local: f64 = w.into_par_iter().map(|&el| el * el).sum();
+    #[cfg(target_arch = "wasm32")]
+    let local: f64 = w.iter().map(|&el| el * el).sum();
+    transport.allreduce_sum(local)
 }
 
-/// Get v infinity
-fn get_v_infinity_expectation_value(w: &ArrayView3<f64>, config: &Config) -> f64 {
+/// Get v infinity. `z_offset` maps `w`'s local work-area z-index back to
+/// this rank's slice of `config`'s global grid (0 for a whole-grid `w`).
+fn get_v_infinity_expectation_value(w: &ArrayView3<f64>,
+                                     config: &Config,
+                                     transport: &Transport,
+                                     z_offset: usize)
+                                     -> f64 {
     //NOTE: No complex conjugation due to all real input for now
-    let mut work = Array3::<f64>::zeros(w.dim());
-    Zip::indexed(&mut work)
-        .and(w)
-        .par_apply(|(i, j, k), work, &w| {
-                       let idx = Index3 { x: i, y: j, z: k };
-                       let potsub = match potential::potential_sub(config, &idx) {
-                           Ok(p) => p,
-                           Err(err) => panic!("Error: {}", err),
-                       };
-                       *work = w * w * potsub;
-                   });
-    work.scalar_sum()
+    let mut work = AlignedArray3::zeros(w.dim(), config.align_bytes);
+    par_apply!(Zip::indexed(work.view_mut()).and(w),
+               |(i, j, k), work, &w| {
+                   let idx = Index3 { x: i, y: j, z: k + z_offset };
+                   let potsub = match potential::potential_sub(config, &idx) {
+                       Ok(p) => p,
+                       Err(err) => panic!("Error: {}", err),
+                   };
+                   *work = w * w * potsub;
+               });
+    transport.allreduce_sum(work.view().scalar_sum())
 }
 
-/// Get r2
-fn get_r_squared_expectation_value(w: &ArrayView3<f64>, grid: &Grid) -> f64 {
+/// Get r2. `z_offset` maps `w`'s local work-area z-index back to this
+/// rank's slice of `config`'s global grid (0 for a whole-grid `w`).
+fn get_r_squared_expectation_value(w: &ArrayView3<f64>,
+                                    config: &Config,
+                                    transport: &Transport,
+                                    z_offset: usize)
+                                    -> f64 {
     //NOTE: No complex conjugation due to all real input for now
-    let mut work = Array3::<f64>::zeros(w.dim());
-    Zip::indexed(&mut work)
-        .and(w)
-        .par_apply(|(i, j, k), work, &w| {
-                       let idx = Index3 { x: i, y: j, z: k };
-                       let r2 = potential::calculate_r2(&idx, grid);
-                       *work = w * w * r2;
-                   });
-    work.scalar_sum()
+    let mut work = AlignedArray3::zeros(w.dim(), config.align_bytes);
+    par_apply!(Zip::indexed(work.view_mut()).and(w),
+               |(i, j, k), work, &w| {
+                   let idx = Index3 { x: i, y: j, z: k + z_offset };
+                   let r2 = potential::calculate_r2(&idx, &config.grid);
+                   *work = w * w * r2;
+               });
+    transport.allreduce_sum(work.view().scalar_sum())
 }
 
 /// Gets energy of the corresponding wavefunction
 //TODO: We can probably drop the config requirement and replace it with a grid modifier of dn*mass
-fn wfnc_energy(config: &Config, params: &Params) -> f64 {
+fn wfnc_energy(config: &Config, params: &Params, transport: &Transport) -> f64 {
 
-    let w = get_work_area(params.phi);
-    let v = get_work_area(&params.potentials.v);
+    let w = get_work_area(params.phi.view());
+    let v = get_work_area(params.potentials.v.view());
+    let phi_view = params.phi.view();
 
     // Simplify what we can here.
     let denominator = 360. * config.grid.dn.powi(2) * config.mass;
 
-    let mut work = Array3::<f64>::zeros(w.dim());
+    let mut work = AlignedArray3::zeros(w.dim(), config.align_bytes);
     //NOTE: TODO: We don't have any complex conjugation here.
     // Complete matrix multiplication step using 7 point central differenc
     // TODO: Option for 3 or 5 point caclulation
-    Zip::indexed(&mut work)
-        .and(v)
-        .and(w)
-        .par_apply(|(i, j, k), work, &v, &w| {
-            // Offset indexes as we are already in a slice
-            let lx = i as isize + 3;
-            let ly = j as isize + 3;
-            let lz = k as isize + 3;
-            let o = 3;
-            // get a slice which gives us our matrix of central difference points
-            let l = params
-                .phi
-                .slice(s![lx - 3..lx + 4, ly - 3..ly + 4, lz - 3..lz + 4]);
-            // l can now be indexed with local offset `o` and modifiers
-            *work = v * w * w -
-                    w *
-                    (2. * l[[o + 3, o, o]] - 27. * l[[o + 2, o, o]] + 270. * l[[o + 1, o, o]] +
-                     270. * l[[o - 1, o, o]] -
-                     27. * l[[o - 2, o, o]] + 2. * l[[o - 3, o, o]] +
-                     2. * l[[o, o + 3, o]] - 27. * l[[o, o + 2, o]] +
-                     270. * l[[o, o + 1, o]] +
-                     270. * l[[o, o - 1, o]] -
-                     27. * l[[o, o - 2, o]] + 2. * l[[o, o - 3, o]] +
-                     2. * l[[o, o, o + 3]] - 27. * l[[o, o, o + 2]] +
-                     270. * l[[o, o, o + 1]] +
-                     270. * l[[o, o, o - 1]] -
-                     27. * l[[o, o, o - 2]] + 2. * l[[o, o, o - 3]] -
-                     1470. * w) / denominator;
-        });
+    par_apply!(Zip::indexed(work.view_mut()).and(v).and(w),
+               |(i, j, k), work, &v, &w| {
+        // Offset indexes as we are already in a slice
+        let lx = i as isize + 3;
+        let ly = j as isize + 3;
+        let lz = k as isize + 3;
+        let o = 3;
+        // get a slice which gives us our matrix of central difference points
+        let l = phi_view.slice(s![lx - 3..lx + 4, ly - 3..ly + 4, lz - 3..lz + 4]);
+        // l can now be indexed with local offset `o` and modifiers
+        *work = v * w * w -
+                w *
+                (2. * l[[o + 3, o, o]] - 27. * l[[o + 2, o, o]] + 270. * l[[o + 1, o, o]] +
+                 270. * l[[o - 1, o, o]] -
+                 27. * l[[o - 2, o, o]] + 2. * l[[o - 3, o, o]] +
+                 2. * l[[o, o + 3, o]] - 27. * l[[o, o + 2, o]] +
+                 270. * l[[o, o + 1, o]] +
+                 270. * l[[o, o - 1, o]] -
+                 27. * l[[o, o - 2, o]] + 2. * l[[o, o - 3, o]] +
+                 2. * l[[o, o, o + 3]] - 27. * l[[o, o, o + 2]] +
+                 270. * l[[o, o, o + 1]] +
+                 270. * l[[o, o, o - 1]] -
+                 27. * l[[o, o, o - 2]] + 2. * l[[o, o, o - 3]] -
+                 1470. * w) / denominator;
+    });
     // Sum result for total energy.
-    work.scalar_sum()
+    transport.allreduce_sum(work.view().scalar_sum())
 }
 
 /// Normalisation of the wavefunction
-fn normalise_wavefunction(w: &mut Array3<f64>, norm2: f64) {
+fn normalise_wavefunction(w: &mut AlignedArray3, norm2: f64) {
     //TODO: This can be moved directly into the calculation for now. It's only here due to normalisationCollect
     let norm = norm2.sqrt();
-    w.par_map_inplace(|el| *el /= norm);
+    par_apply!(Zip::from(w.view_mut()), |w| *w /= norm);
 }
 
 /// Uses Gram Schmidt orthogonalisation to identify the next excited state's wavefunction, even if it's degenerate
-fn orthogonalise_wavefunction(wnum: u8, w: &mut Array3<f64>, w_store: &Vec<Array3<f64>>) {
+fn orthogonalise_wavefunction(wnum: u8,
+                               w: &mut AlignedArray3,
+                               w_store: &Vec<AlignedArray3>,
+                               transport: &Transport) {
     for idx in 0..wnum as usize {
         let lower = &w_store[idx];
-        let overlap = (lower * &w.view()).scalar_sum(); //TODO: par this multiplication if possible. A temp work array and par_applied zip is slower, even with an unassigned array
-        Zip::from(w.view_mut())
-            .and(lower)
-            .par_apply(|w, &lower| *w -= lower * overlap);
+        let local_overlap = (&lower.view() * &w.view()).scalar_sum(); //TODO: par this multiplication if possible. A temp work array and par_applied zip is slower, even with an unassigned array
+        let overlap = transport.allreduce_sum(local_overlap);
+        par_apply!(Zip::from(w.view_mut()).and(lower.view()),
+                   |w, &lower| *w -= lower * overlap);
     }
 }
 
-fn get_work_area(w: &Array3<f64>) -> ArrayView3<f64> {
+fn get_work_area(w: ArrayView3<f64>) -> ArrayView3<f64> {
     // TODO: This is hardcoded to a 7 point stencil
     let dims = w.dim();
-    w.slice(s![3..(dims.0 as isize) - 3,
-               3..(dims.1 as isize) - 3,
-               3..(dims.2 as isize) - 3])
+    w.slice_move(s![3..(dims.0 as isize) - 3,
+                    3..(dims.1 as isize) - 3,
+                    3..(dims.2 as isize) - 3])
 }
 
-fn get_mut_work_area(w: &mut Array3<f64>) -> ArrayViewMut3<f64> {
+/// `pub(crate)`, not private: `input::parse_csv_to_array3` also needs to
+/// fill just the work area of a freshly loaded/resampled CSV buffer.
+pub(crate) fn get_mut_work_area(w: ArrayViewMut3<f64>) -> ArrayViewMut3<f64> {
     // TODO: This is hardcoded to a 7 point stencil
     let dims = w.dim();
-    w.slice_mut(s![3..(dims.0 as isize) - 3,
-                   3..(dims.1 as isize) - 3,
-                   3..(dims.2 as isize) - 3])
+    w.slice_move(s![3..(dims.0 as isize) - 3,
+                    3..(dims.1 as isize) - 3,
+                    3..(dims.2 as isize) - 3])
 }
 
 /// Evolves the solution a number of `steps`
-fn evolve(wnum: u8, config: &Config, params: &mut Params, w_store: &Vec<Array3<f64>>) {
-    //without mpi, this is just update interior (which is really updaterule if we dont need W)
+fn evolve(wnum: u8,
+          config: &Config,
+          params: &mut Params,
+          w_store: &Vec<AlignedArray3>,
+          transport: &Transport) {
+    //without mpi, this is just update interior (which is really updaterule if we dont need W).
+    //With mpi/zeromq, each rank owns a contiguous z-slab and we exchange the
+    //HALO_WIDTH boundary planes with our neighbours after every step below.
 
     let mut work_dims = params.phi.dim();
     work_dims.0 -= 6;
@@ -323,60 +689,58 @@ fn evolve(wnum: u8, config: &Config, params: &mut Params, w_store: &Vec<Array3<f
     let mut steps = 0;
     loop {
 
-        let mut work = Array3::<f64>::zeros(work_dims);
+        let mut work = AlignedArray3::zeros(work_dims, config.align_bytes);
         {
-            let w = get_work_area(params.phi);
-            let a = get_work_area(&params.potentials.a);
-            let b = get_work_area(&params.potentials.b);
+            let phi_view = params.phi.view();
+            let w = get_work_area(phi_view);
+            let a = get_work_area(params.potentials.a.view());
+            let b = get_work_area(params.potentials.b.view());
 
             let denominator = 360. * config.grid.dn.powi(2) * config.mass;
 
             //NOTE: TODO: We don't have any complex conjugation here.
             // Complete matrix multiplication step using 7 point central difference
             // TODO: Option for 3 or 5 point caclulation
-            Zip::indexed(&mut work)
-                .and(a)
-                .and(b)
-                .and(w)
-                .par_apply(|(i, j, k), work, &a, &b, &w| {
-                    // Offset indexes as we are already in a slice
-                    let lx = i as isize + 3;
-                    let ly = j as isize + 3;
-                    let lz = k as isize + 3;
-                    let o = 3;
-                    // get a slice which gives us our matrix of central difference points
-                    let l = params
-                        .phi
-                        .slice(s![lx - 3..lx + 4, ly - 3..ly + 4, lz - 3..lz + 4]);
-                    // l can now be indexed with local offset `o` and modifiers
-                    *work =
-                        w * a +
-                        b * config.grid.dt *
-                        (2. * l[[o + 3, o, o]] - 27. * l[[o + 2, o, o]] + 270. * l[[o + 1, o, o]] +
-                         270. * l[[o - 1, o, o]] - 27. * l[[o - 2, o, o]] +
-                         2. * l[[o - 3, o, o]] + 2. * l[[o, o + 3, o]] -
-                         27. * l[[o, o + 2, o]] + 270. * l[[o, o + 1, o]] +
-                         270. * l[[o, o - 1, o]] - 27. * l[[o, o - 2, o]] +
-                         2. * l[[o, o - 3, o]] + 2. * l[[o, o, o + 3]] -
-                         27. * l[[o, o, o + 2]] + 270. * l[[o, o, o + 1]] +
-                         270. * l[[o, o, o - 1]] - 27. * l[[o, o, o - 2]] +
-                         2. * l[[o, o, o - 3]] - 1470. * w) / denominator;
-                });
+            par_apply!(Zip::indexed(work.view_mut()).and(a).and(b).and(w),
+                       |(i, j, k), work, &a, &b, &w| {
+                // Offset indexes as we are already in a slice
+                let lx = i as isize + 3;
+                let ly = j as isize + 3;
+                let lz = k as isize + 3;
+                let o = 3;
+                // get a slice which gives us our matrix of central difference points
+                let l = phi_view.slice(s![lx - 3..lx + 4, ly - 3..ly + 4, lz - 3..lz + 4]);
+                // l can now be indexed with local offset `o` and modifiers
+                *work =
+                    w * a +
+                    b * config.grid.dt *
+                    (2. * l[[o + 3, o, o]] - 27. * l[[o + 2, o, o]] + 270. * l[[o + 1, o, o]] +
+                     270. * l[[o - 1, o, o]] - 27. * l[[o - 2, o, o]] +
+                     2. * l[[o - 3, o, o]] + 2. * l[[o, o + 3, o]] -
+                     27. * l[[o, o + 2, o]] + 270. * l[[o, o + 1, o]] +
+                     270. * l[[o, o - 1, o]] - 27. * l[[o, o - 2, o]] +
+                     2. * l[[o, o - 3, o]] + 2. * l[[o, o, o + 3]] -
+                     27. * l[[o, o, o + 2]] + 270. * l[[o, o, o + 1]] +
+                     270. * l[[o, o, o - 1]] - 27. * l[[o, o, o - 2]] +
+                     2. * l[[o, o, o - 3]] - 1470. * w) / denominator;
+            });
         }
         {
-            let mut w_fill = get_mut_work_area(params.phi);
-            Zip::from(&mut w_fill)
-                .and(&work)
-                .par_apply(|w_fill, &work| { *w_fill = work; });
+            let mut w_fill = get_mut_work_area(params.phi.view_mut());
+            par_apply!(Zip::from(&mut w_fill).and(work.view()),
+                       |w_fill, &work| { *w_fill = work; });
         }
+        // Interior is up to date; before the next step's stencil reads across
+        // a slab boundary, pull in the neighbours' fresh HALO_WIDTH planes.
+        transport.exchange_halo(params.phi.view_mut());
         if wnum > 0 {
             let norm2: f64;
             {
-                let work = get_work_area(params.phi);
-                norm2 = get_norm_squared(&work);
+                let work = get_work_area(params.phi.view());
+                norm2 = get_norm_squared(&work, transport);
             }
             normalise_wavefunction(params.phi, norm2);
-            orthogonalise_wavefunction(wnum, params.phi, w_store);
+            orthogonalise_wavefunction(wnum, params.phi, w_store, transport);
         }
         steps += 1;
         if steps >= config.output.screen_update {
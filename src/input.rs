@@ -5,7 +5,9 @@ use std::io::{Error, ErrorKind};
 use std::path::Path;
 use ndarray::{Array3, Zip};
 use ndarray_parallel::prelude::*;
+use align::AlignedArray3;
 use grid;
+use resample::{self, ResampleMode};
 use config::Config;
 
 #[derive(Debug,Deserialize)]
@@ -22,7 +24,10 @@ struct PlainRecord {
 }
 
 /// Loads a wafefunction from a csv file on disk.
-pub fn wavefunction_plain(wnum: u8, target_size: [usize; 3]) -> Result<Array3<f64>, csv::Error> {
+pub fn wavefunction_plain(wnum: u8,
+                          target_size: [usize; 3],
+                          align_bytes: usize)
+                          -> Result<AlignedArray3, csv::Error> {
     let filename = format!("./input/wavefunction_{}.csv", wnum);
     let filename_parital = format!("./input/wavefunction_{}_partial.csv", wnum);
     let file = if Path::new(&filename).exists() {
@@ -32,30 +37,30 @@ pub fn wavefunction_plain(wnum: u8, target_size: [usize; 3]) -> Result<Array3<f6
     } else {
         None
     };
-    parse_csv_to_array3(file, target_size)
+    parse_csv_to_array3(file, target_size, align_bytes)
 }
 
 /// Loads a potential from a csv file on disk.
-pub fn potential_plain(target_size: [usize; 3]) -> Result<Array3<f64>, csv::Error> {
+pub fn potential_plain(target_size: [usize; 3], align_bytes: usize) -> Result<AlignedArray3, csv::Error> {
     let filename = "./input/potential.csv";
     let file = if Path::new(&filename).exists() {
         Some(filename.to_string())
     } else {
         None
     };
-    parse_csv_to_array3(file, target_size)
+    parse_csv_to_array3(file, target_size, align_bytes)
 }
 
 
 /// Loads previously computed wavefunctions from disk.
-pub fn load_wavefunctions(config: &Config, log: &Logger, w_store: &mut Vec<Array3<f64>>) {
+pub fn load_wavefunctions(config: &Config, log: &Logger, w_store: &mut Vec<AlignedArray3>) {
     let num = &config.grid.size;
     let init_size: [usize; 3] = [(num.x + 6) as usize,
                                  (num.y + 6) as usize,
                                  (num.z + 6) as usize];
     // Load required wavefunctions. If the current state resides on disk as well, we load that later.
     for wnum in 0..config.wavenum {
-        let wfn = wavefunction_plain(wnum, init_size);
+        let wfn = wavefunction_plain(wnum, init_size, config.align_bytes);
         match wfn {
             Ok(w) => w_store.push(w),
             Err(err) => {
@@ -96,14 +101,18 @@ pub fn check_input_dir() {
 /// which may not be able to obtain a valid location.
 /// * `target_size` - Requsted size of the resultant array. If this size does not match the data
 /// pulled from the file, interpolation or resampling will occur.
+/// * `align_bytes` - Alignment (in bytes) for the returned buffer, so CSV-loaded wavefunctions
+/// and potentials land in the same kind of aligned storage as everything else feeding the
+/// stencil kernels, instead of needing a separate `AlignedArray3::from_array3` copy afterwards.
 ///
 /// # Returns
 ///
 /// * A 3D array loaded with data from the file and resampled/interpolated if required.
 /// If something goes wrong in the parsing or file handling, a `csv::Error` is passed.
 fn parse_csv_to_array3(file: Option<String>,
-                       target_size: [usize; 3])
-                       -> Result<Array3<f64>, csv::Error> {
+                        target_size: [usize; 3],
+                        align_bytes: usize)
+                        -> Result<AlignedArray3, csv::Error> {
     match file {
         Some(f) => {
             let mut rdr = csv::ReaderBuilder::new().has_headers(false).from_path(f)?;
@@ -127,37 +136,43 @@ fn parse_csv_to_array3(file: Option<String>,
             let numx = max_i + 1;
             let numy = max_j + 1;
             let numz = max_k + 1;
+            // The ghost region is a fixed 6 cells (3 per side); anything
+            // smaller can't hold a work area at all.
+            if target_size.iter().any(|&d| d < 6) {
+                return Err(csv::Error::from(Error::new(ErrorKind::InvalidInput,
+                                                        "target size must be at least 6 cells per axis")));
+            }
             match Array3::<f64>::from_shape_vec((numx, numy, numz), data) {
                 Ok(result) => {
                     //result is now a parsed Array3 with the work area inside.
-                    //We must fill this into an array with CD boundaries, provided
-                    //it is the correct size. If not, we must scale it.
-                    let init_size: [usize; 3] = [numx + 6, numy + 6, numz + 6];
-                    let mut complete = Array3::<f64>::zeros(target_size);
+                    //We must fill this into an array with CD boundaries, resampling
+                    //if the source isn't already the size we need.
+                    let mut complete = AlignedArray3::zeros((target_size[0], target_size[1], target_size[2]),
+                                                             align_bytes);
                     {
-                        let mut work = grid::get_mut_work_area(&mut complete);
-                        let same: bool = init_size
-                            .iter()
-                            .zip(target_size.iter())
-                            .all(|(a, b)| a == b);
-                        let smaller: bool =
-                            init_size.iter().zip(target_size.iter()).all(|(a, b)| a < b);
-                        let larger: bool =
-                            init_size.iter().zip(target_size.iter()).all(|(a, b)| a > b);
-                        if same {
-                            // Input is the same size, copy down.
+                        let mut work = grid::get_mut_work_area(complete.view_mut());
+                        let work_dims = work.dim();
+                        if (numx, numy, numz) == work_dims {
+                            // Input is the same size, copy down directly.
                             Zip::from(&mut work)
                                 .and(result.view())
                                 .par_apply(|work, &result| *work = result);
-                        } else if smaller {
-                            //TODO: Input has lower resolution. Spread it out.
-                            panic!("Wavefunction is lower in resolution than requested");
-                        } else if larger {
-                            //TODO: Input has higer resolution. Sample it.
-                            panic!("Wavefunction is higher in resolution than requested");
                         } else {
-                            //TODO: Dimensons are all over the shop. Sample and interp
-                            panic!("Wavefunction differs in resolution from requested");
+                            // Anisotropic ratios are handled per axis independently by
+                            // `resample`. Downsampling (any axis shrinking) uses Lanczos-3
+                            // interpolation, otherwise trilinear. Neither mode low-pass
+                            // filters the source first (see `ResampleMode::Lanczos`).
+                            let downsampling = work_dims.0 < numx || work_dims.1 < numy ||
+                                                work_dims.2 < numz;
+                            let mode = if downsampling {
+                                ResampleMode::Lanczos
+                            } else {
+                                ResampleMode::Trilinear
+                            };
+                            let resampled = resample::resample(&result, work_dims, mode);
+                            Zip::from(&mut work)
+                                .and(&resampled)
+                                .par_apply(|work, &resampled| *work = resampled);
                         }
                     }
                     Ok(complete)
@@ -168,3 +183,61 @@ fn parse_csv_to_array3(file: Option<String>,
         None => Err(csv::Error::from(Error::from(ErrorKind::NotFound))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{remove_file, File};
+    use std::io::Write;
+
+    /// Writes a `numx * numy * numz` plain csv (every cell filled with
+    /// `value`) to a fresh temp path and returns it, so each test gets its
+    /// own file instead of racing others over a shared name.
+    fn write_plain_csv(name: &str, numx: usize, numy: usize, numz: usize, value: f64) -> String {
+        let path = ::std::env::temp_dir().join(format!("wafer_test_{}.csv", name));
+        let path = path.to_str().unwrap().to_string();
+        let mut f = File::create(&path).unwrap();
+        for i in 0..numx {
+            for j in 0..numy {
+                for k in 0..numz {
+                    writeln!(f, "{},{},{},{}", i, j, k, value).unwrap();
+                }
+            }
+        }
+        path
+    }
+
+    #[test]
+    fn target_size_below_six_is_an_err_not_a_panic() {
+        let path = write_plain_csv("below_six", 2, 2, 2, 1.0);
+        let result = parse_csv_to_array3(Some(path.clone()), [4, 8, 8], 32);
+        remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn mismatched_size_upsamples_instead_of_panicking() {
+        let path = write_plain_csv("upsample", 2, 2, 2, 3.0);
+        // target_size includes the 3-cell ghost region per side, so a (2,2,2)
+        // source lands in a (6,6,6) work area here.
+        let result = parse_csv_to_array3(Some(path.clone()), [12, 12, 12], 32);
+        remove_file(&path).unwrap();
+        let mut array = result.unwrap();
+        let work = grid::get_mut_work_area(array.view_mut());
+        assert_eq!(work.dim(), (6, 6, 6));
+        // The source was a constant field, so every resampled value should
+        // still come out at (around) that same constant.
+        assert!(work.iter().all(|&v| (v - 3.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn mismatched_size_downsamples_instead_of_panicking() {
+        let path = write_plain_csv("downsample", 10, 10, 10, 5.0);
+        let result = parse_csv_to_array3(Some(path.clone()), [9, 9, 9], 32);
+        remove_file(&path).unwrap();
+        let mut array = result.unwrap();
+        let work = grid::get_mut_work_area(array.view_mut());
+        assert_eq!(work.dim(), (3, 3, 3));
+        assert!(work.iter().all(|&v| (v - 5.0).abs() < 1e-9));
+    }
+}
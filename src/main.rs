@@ -1,5 +1,11 @@
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
+mod align;
+mod resample;
+mod stats;
+mod transport;
+mod wasm;
+
 fn main() {
 
     println!("                    ___");
@@ -0,0 +1,185 @@
+use ndarray::{Array3, Zip};
+use ndarray_parallel::prelude::*;
+
+/// How source data is resampled onto a differently-shaped target grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResampleMode {
+    /// Separable trilinear interpolation (eight-neighbour weighted blend).
+    /// Cheap, and the right choice when upsampling onto a finer grid.
+    Trilinear,
+    /// Separable Lanczos-3 interpolation: a six-tap-per-axis, higher-order
+    /// blend than trilinear, and slower. The kernel is evaluated at
+    /// source-cell spacing and is not widened by the downsampling ratio, so
+    /// source content above the target grid's Nyquist limit is not filtered
+    /// out before sampling.
+    Lanczos,
+}
+
+/// Resamples `source` onto a grid of shape `target_dims`, mapping each
+/// target cell centre back to a fractional source coordinate. Axes are
+/// handled independently, so anisotropic ratios (e.g. 2x in x, 1x in z)
+/// work without any special-casing.
+pub fn resample(source: &Array3<f64>, target_dims: (usize, usize, usize), mode: ResampleMode) -> Array3<f64> {
+    if source.dim() == target_dims {
+        return source.clone();
+    }
+    let source_dims = source.dim();
+    let xs = source_coords(source_dims.0, target_dims.0);
+    let ys = source_coords(source_dims.1, target_dims.1);
+    let zs = source_coords(source_dims.2, target_dims.2);
+
+    let mut out = Array3::<f64>::zeros(target_dims);
+    Zip::indexed(&mut out).par_apply(|(i, j, k), el| {
+        let (fx, fy, fz) = (xs[i], ys[j], zs[k]);
+        *el = match mode {
+            ResampleMode::Trilinear => trilinear_sample(source, fx, fy, fz),
+            ResampleMode::Lanczos => lanczos_sample(source, fx, fy, fz, 3),
+        };
+    });
+    out
+}
+
+/// Fractional source-axis coordinate of each of `target_len` cell centres,
+/// clamped so the last target cell always lands exactly on the last source
+/// cell rather than reading past the edge.
+fn source_coords(source_len: usize, target_len: usize) -> Vec<f64> {
+    if target_len <= 1 || source_len <= 1 {
+        return vec![0.; target_len];
+    }
+    let scale = (source_len - 1) as f64 / (target_len - 1) as f64;
+    (0..target_len).map(|t| (t as f64 * scale).min((source_len - 1) as f64)).collect()
+}
+
+/// Eight-neighbour weighted blend at a fractional `(fx, fy, fz)` source
+/// coordinate, clamping at the edges.
+fn trilinear_sample(source: &Array3<f64>, fx: f64, fy: f64, fz: f64) -> f64 {
+    let dims = source.dim();
+    let x0 = fx.floor() as usize;
+    let y0 = fy.floor() as usize;
+    let z0 = fz.floor() as usize;
+    let x1 = (x0 + 1).min(dims.0 - 1);
+    let y1 = (y0 + 1).min(dims.1 - 1);
+    let z1 = (z0 + 1).min(dims.2 - 1);
+    let tx = fx - x0 as f64;
+    let ty = fy - y0 as f64;
+    let tz = fz - z0 as f64;
+
+    let c00 = source[[x0, y0, z0]] * (1. - tx) + source[[x1, y0, z0]] * tx;
+    let c10 = source[[x0, y1, z0]] * (1. - tx) + source[[x1, y1, z0]] * tx;
+    let c01 = source[[x0, y0, z1]] * (1. - tx) + source[[x1, y0, z1]] * tx;
+    let c11 = source[[x0, y1, z1]] * (1. - tx) + source[[x1, y1, z1]] * tx;
+
+    let c0 = c00 * (1. - ty) + c10 * ty;
+    let c1 = c01 * (1. - ty) + c11 * ty;
+
+    c0 * (1. - tz) + c1 * tz
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0. {
+        1.
+    } else {
+        let px = ::std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Lanczos-`a` kernel, zero outside its `[-a, a]` support.
+fn lanczos_kernel(x: f64, a: f64) -> f64 {
+    if x.abs() >= a {
+        0.
+    } else {
+        sinc(x) * sinc(x / a)
+    }
+}
+
+/// Separable Lanczos-`a` resample at a fractional `(fx, fy, fz)` source
+/// coordinate. Taps that fall outside the source are clamped to the
+/// nearest edge cell, and the result is renormalised by the sum of every
+/// tap's weight (clamped taps included) so edges don't darken/brighten.
+fn lanczos_sample(source: &Array3<f64>, fx: f64, fy: f64, fz: f64, a: i64) -> f64 {
+    let dims = source.dim();
+    let clamp = |v: i64, len: usize| v.max(0).min(len as i64 - 1) as usize;
+    let taps = |centre: f64| -> Vec<(i64, f64)> {
+        let base = centre.floor() as i64;
+        (-a + 1..=a)
+            .map(|offset| {
+                     let idx = base + offset;
+                     (idx, lanczos_kernel(centre - idx as f64, a as f64))
+                 })
+            .collect()
+    };
+    let xs = taps(fx);
+    let ys = taps(fy);
+    let zs = taps(fz);
+
+    let mut acc = 0.;
+    let mut weight_sum = 0.;
+    for &(xi, wx) in &xs {
+        for &(yi, wy) in &ys {
+            for &(zi, wz) in &zs {
+                let w = wx * wy * wz;
+                let v = source[[clamp(xi, dims.0), clamp(yi, dims.1), clamp(zi, dims.2)]];
+                acc += w * v;
+                weight_sum += w;
+            }
+        }
+    }
+    if weight_sum.abs() > 1e-12 {
+        acc / weight_sum
+    } else {
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_to_the_same_shape_is_a_plain_copy() {
+        let source = Array3::<f64>::from_shape_vec((2, 2, 2), (0..8).map(|v| v as f64).collect()).unwrap();
+        let out = resample(&source, (2, 2, 2), ResampleMode::Trilinear);
+        assert_eq!(out, source);
+    }
+
+    #[test]
+    fn source_coords_clamps_the_last_target_cell_onto_the_last_source_cell() {
+        let coords = source_coords(4, 7);
+        assert_eq!(coords[0], 0.);
+        assert_eq!(*coords.last().unwrap(), 3.);
+    }
+
+    #[test]
+    fn source_coords_of_a_single_cell_axis_is_all_zero() {
+        assert_eq!(source_coords(5, 1), vec![0.]);
+        assert_eq!(source_coords(1, 5), vec![0.; 5]);
+    }
+
+    #[test]
+    fn trilinear_sample_at_a_source_node_returns_that_node_exactly() {
+        let source = Array3::<f64>::from_shape_vec((2, 2, 2), (0..8).map(|v| v as f64).collect()).unwrap();
+        assert_eq!(trilinear_sample(&source, 1., 1., 1.), source[[1, 1, 1]]);
+    }
+
+    #[test]
+    fn trilinear_sample_interpolates_linearly_between_two_nodes() {
+        let source = Array3::<f64>::from_shape_vec((2, 1, 1), vec![0., 10.]).unwrap();
+        assert_eq!(trilinear_sample(&source, 0.5, 0., 0.), 5.);
+    }
+
+    #[test]
+    fn lanczos_sample_at_an_interior_source_node_returns_that_node_exactly() {
+        let data: Vec<f64> = (0..9).map(|v| v as f64).collect();
+        let source = Array3::<f64>::from_shape_vec((9, 1, 1), data).unwrap();
+        let value = lanczos_sample(&source, 4., 0., 0., 3);
+        assert!((value - 4.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn resample_upsamples_to_the_requested_shape() {
+        let source = Array3::<f64>::from_shape_vec((2, 2, 2), (0..8).map(|v| v as f64).collect()).unwrap();
+        let out = resample(&source, (4, 4, 4), ResampleMode::Lanczos);
+        assert_eq!(out.dim(), (4, 4, 4));
+    }
+}
@@ -0,0 +1,284 @@
+//! Small self-contained statistics helpers for turning a window of
+//! per-snapshot `norm_energy` samples into a mean and a defensible
+//! confidence interval, used by `grid::run` to report excitation energies
+//! `E_n - E_0` alongside an error bar instead of a single bare number.
+
+/// Sample mean, used as the point estimate of the converged energy.
+fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// `k`-th central sample moment about `mu`.
+fn central_moment(samples: &[f64], mu: f64, k: i32) -> f64 {
+    samples.iter().map(|&x| (x - mu).powi(k)).sum::<f64>() / samples.len() as f64
+}
+
+/// Regularized lower incomplete gamma function `P(a, x)`, via its series
+/// expansion. Good enough for the small integer/half-integer `a` the
+/// chi-squared CDF below needs.
+fn lower_incomplete_gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    let mut term = 1. / a;
+    let mut sum = term;
+    let mut n = 1.;
+    while term.abs() > sum.abs() * 1e-12 && n < 1000. {
+        term *= x / (a + n);
+        sum += term;
+        n += 1.;
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+/// Lanczos approximation (g = 7) of `ln(Gamma(x))`, accurate for the small
+/// positive arguments used here (chi-squared/t degrees of freedom).
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.;
+    const COEFFS: [f64; 9] = [0.99999999999980993,
+                              676.5203681218851,
+                              -1259.1392167224028,
+                              771.32342877765313,
+                              -176.61502916214059,
+                              12.507343278686905,
+                              -0.13857109526572012,
+                              9.9843695780195716e-6,
+                              1.5056327351493116e-7];
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let mut a = COEFFS[0];
+    let t = x + G + 0.5;
+    for (i, coeff) in COEFFS.iter().enumerate().skip(1) {
+        a += coeff / (x + i as f64);
+    }
+    0.5 * (2. * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + a.ln()
+}
+
+/// CDF of the chi-squared distribution with `dof` degrees of freedom.
+fn chi_squared_cdf(x: f64, dof: f64) -> f64 {
+    lower_incomplete_gamma_p(dof / 2., x / 2.)
+}
+
+/// p-value of the Jarque-Bera statistic, which is asymptotically
+/// chi-squared distributed with 2 degrees of freedom under the null
+/// hypothesis that the samples are normal.
+fn jarque_bera_p_value(jb: f64) -> f64 {
+    1. - chi_squared_cdf(jb, 2.)
+}
+
+/// Inverse CDF (quantile function) of the Student-t distribution with
+/// `dof` degrees of freedom, found by bisection on the (monotonic) CDF
+/// via the regularized incomplete beta function's relation to it.
+fn student_t_inv_cdf(p: f64, dof: f64) -> f64 {
+    let target = p;
+    let (mut lo, mut hi) = (-1.0e4, 1.0e4);
+    for _ in 0..200 {
+        let mid = 0.5 * (lo + hi);
+        if student_t_cdf(mid, dof) < target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+/// CDF of the Student-t distribution, expressed via the regularized
+/// incomplete beta function `I_x(dof/2, 1/2)`.
+fn student_t_cdf(t: f64, dof: f64) -> f64 {
+    let x = dof / (dof + t * t);
+    let tail = 0.5 * regularized_incomplete_beta(x, dof / 2., 0.5);
+    if t < 0. { tail } else { 1. - tail }
+}
+
+/// Regularized incomplete beta function via a continued fraction
+/// (Numerical Recipes' `betacf`), sufficient precision for the `t`
+/// quantiles used to build confidence intervals here.
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let ln_beta = ln_gamma(a) + ln_gamma(b) - ln_gamma(a + b);
+    let front = (a * x.ln() + b * (1. - x).ln() - ln_beta).exp();
+    if x < (a + 1.) / (a + b + 2.) {
+        front * betacf(x, a, b) / a
+    } else {
+        1. - front * betacf(1. - x, b, a) / b
+    }
+}
+
+fn betacf(x: f64, a: f64, b: f64) -> f64 {
+    const MAX_ITER: i32 = 200;
+    const EPS: f64 = 3.0e-14;
+    let qab = a + b;
+    let qap = a + 1.;
+    let qam = a - 1.;
+    let mut c = 1.;
+    let mut d = 1. - qab * x / qap;
+    if d.abs() < 1e-30 {
+        d = 1e-30;
+    }
+    d = 1. / d;
+    let mut h = d;
+    for m in 1..MAX_ITER {
+        let m = m as f64;
+        let m2 = 2. * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1. + aa * d;
+        if d.abs() < 1e-30 {
+            d = 1e-30;
+        }
+        c = 1. + aa / c;
+        if c.abs() < 1e-30 {
+            c = 1e-30;
+        }
+        d = 1. / d;
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1. + aa * d;
+        if d.abs() < 1e-30 {
+            d = 1e-30;
+        }
+        c = 1. + aa / c;
+        if c.abs() < 1e-30 {
+            c = 1e-30;
+        }
+        d = 1. / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.).abs() < EPS {
+            break;
+        }
+    }
+    h
+}
+
+/// Mean and half-width of a confidence interval over `samples`, the
+/// per-snapshot `norm_energy` values recorded over the final converged
+/// window for one state.
+pub struct EnergyEstimate {
+    pub mean: f64,
+    pub err: f64,
+}
+
+/// Runs a Jarque-Bera normality check (`JB = (n/6)(S^2 + (K-3)^2/4)` from
+/// the sample skewness `S` and kurtosis `K`); if the samples pass it at
+/// `confidence`, the half-width is the usual `t * sqrt(sigma2/n)` Student-t
+/// interval. Otherwise we can't trust that interval, so we widen it to the
+/// full observed spread instead of understating the uncertainty.
+pub fn estimate(samples: &[f64], confidence: f64) -> EnergyEstimate {
+    let n = samples.len();
+    assert!(n > 1, "need at least two samples to estimate an error bar");
+    let mu = mean(samples);
+    let sigma2 = central_moment(samples, mu, 2);
+    if sigma2 == 0.0 {
+        // Every sample is identical: skewness/kurtosis would divide 0. by 0.
+        // into NaN, and NaN > alpha is always false, so this would otherwise
+        // only fall into the non-normal branch by accident of float
+        // comparison semantics. There's no spread to report either way.
+        return EnergyEstimate { mean: mu, err: 0.0 };
+    }
+    let m3 = central_moment(samples, mu, 3);
+    let m4 = central_moment(samples, mu, 4);
+
+    let skewness = m3 / sigma2.powf(1.5);
+    let kurtosis = m4 / sigma2.powi(2);
+    let n_f = n as f64;
+    let jb = (n_f / 6.) * (skewness.powi(2) + (kurtosis - 3.).powi(2) / 4.);
+    let p_value = jarque_bera_p_value(jb);
+
+    let alpha = 1. - confidence;
+    let err = if p_value > alpha {
+        let t = student_t_inv_cdf(1. - alpha / 2., n_f - 1.);
+        t * (sigma2 / n_f).sqrt()
+    } else {
+        // Non-normal: fall back to the full observed spread around the mean
+        // rather than trusting a Gaussian-shaped interval.
+        samples.iter().fold(0.0f64, |acc, &x| acc.max((x - mu).abs()))
+    };
+
+    EnergyEstimate { mean: mu, err: err }
+}
+
+/// Combines a ground-state and excited-state estimate into `ΔE ± err` for
+/// `E_n - E_0`, propagating the error bars in quadrature.
+pub fn excitation_energy(excited: &EnergyEstimate, ground: &EnergyEstimate) -> EnergyEstimate {
+    EnergyEstimate {
+        mean: excited.mean - ground.mean,
+        err: (excited.err.powi(2) + ground.err.powi(2)).sqrt(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_of_constant_samples_is_that_constant() {
+        assert_eq!(mean(&[2., 2., 2., 2.]), 2.);
+    }
+
+    #[test]
+    fn central_moment_of_constant_samples_is_zero() {
+        assert_eq!(central_moment(&[5., 5., 5.], 5., 2), 0.);
+    }
+
+    #[test]
+    fn chi_squared_cdf_is_zero_at_zero_and_tends_to_one() {
+        assert_eq!(chi_squared_cdf(0., 2.), 0.);
+        assert!(chi_squared_cdf(100., 2.) > 0.999);
+    }
+
+    #[test]
+    fn student_t_cdf_is_one_half_at_zero() {
+        assert!((student_t_cdf(0., 10.) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_of_constant_samples_has_zero_error() {
+        let est = estimate(&[1., 1., 1., 1., 1., 1.], 0.95);
+        assert_eq!(est.mean, 1.);
+        assert!(est.err.abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "need at least two samples")]
+    fn estimate_panics_on_a_single_sample() {
+        estimate(&[1.], 0.95);
+    }
+
+    #[test]
+    fn estimate_of_near_gaussian_samples_takes_the_student_t_branch() {
+        // Passes the Jarque-Bera check at 95% confidence, so this should use
+        // the `t * sqrt(sigma2/n)` interval, not the max-abs-deviation fallback.
+        let samples = [-1.2, -0.8, -0.3, 0.1, 0.2, 0.4, 0.6, 0.9, 1.1, -0.1, 0.05, -0.5];
+        let est = estimate(&samples, 0.95);
+        assert!((est.mean - 0.0375).abs() < 1e-9);
+        assert!((est.err - 0.41036000254256405).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_of_a_heavily_bimodal_sample_falls_back_to_max_abs_deviation() {
+        // Fails the Jarque-Bera check badly at 95% confidence, so this should
+        // widen to the full observed spread instead of a Student-t interval.
+        let mut samples = vec![0.0; 18];
+        samples.extend_from_slice(&[100.0, 100.0]);
+        let est = estimate(&samples, 0.95);
+        assert_eq!(est.mean, 10.0);
+        assert_eq!(est.err, 90.0);
+    }
+
+    #[test]
+    fn excitation_energy_subtracts_means_and_quadrature_sums_errors() {
+        let ground = EnergyEstimate { mean: 1., err: 0.3 };
+        let excited = EnergyEstimate { mean: 4., err: 0.4 };
+        let excitation = excitation_energy(&excited, &ground);
+        assert_eq!(excitation.mean, 3.);
+        assert!((excitation.err - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,259 @@
+use ndarray::{Array3, ArrayView3, ArrayViewMut3};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Width (in cells) of the ghost region on each face of a slab, matching
+/// the `+6`/`work_dims -= 6` padding `grid` already carries for the 7-point
+/// stencil.
+pub const HALO_WIDTH: usize = 3;
+
+/// Halo-exchange/allreduce protocol a distributed-memory transport uses to
+/// keep contiguous z-slabs in sync across ranks/workers -- the seam a real
+/// MPI or ZeroMQ transport plugs into, so only the `HALO_WIDTH` boundary
+/// planes are shipped between neighbours rather than the whole slab.
+pub trait Transport {
+    /// 0-indexed rank of this worker within the decomposition.
+    fn rank(&self) -> usize;
+    /// Total number of workers taking part in the decomposition.
+    fn num_ranks(&self) -> usize;
+
+    /// Sends this slab's low/high z boundary planes to its neighbours and
+    /// writes what comes back into the corresponding ghost cells. `phi` is
+    /// the full padded array (work area plus the `HALO_WIDTH`-cell ghost
+    /// region on every side). Takes a view rather than an owned `Array3` so
+    /// both plain and `align::AlignedArray3`-backed buffers can call through
+    /// via their `view_mut()`.
+    fn exchange_halo(&self, phi: ArrayViewMut3<f64>);
+
+    /// Reduces `local` (e.g. a partial `scalar_sum`) across every rank and
+    /// returns the global total to all of them -- the allreduce backing
+    /// `get_norm_squared`, `wfnc_energy`, and the Gram-Schmidt overlap in
+    /// `orthogonalise_wavefunction`.
+    fn allreduce_sum(&self, local: f64) -> f64;
+}
+
+/// Single-process fallback: there are no neighbours to exchange with, so
+/// the ghost region is left exactly as the previous step wrote it.
+#[derive(Debug, Default)]
+pub struct SingleRank;
+
+impl Transport for SingleRank {
+    fn rank(&self) -> usize {
+        0
+    }
+
+    fn num_ranks(&self) -> usize {
+        1
+    }
+
+    fn exchange_halo(&self, _phi: ArrayViewMut3<f64>) {
+        // Nothing to do: there's only one rank, so no neighbour's ghost
+        // planes could ever change what's already here.
+    }
+
+    fn allreduce_sum(&self, local: f64) -> f64 {
+        local
+    }
+}
+
+/// In-process stand-in for a real cross-machine (MPI/ZeroMQ) transport:
+/// each `ThreadRanks` is one rank in a fixed-size ring of workers, wired to
+/// its two z-neighbours over `std::sync::mpsc` (the ring wraps, so the
+/// topology is periodic). `ring` builds one per rank; `grid::run_distributed`
+/// is what actually carves a grid into per-rank z-slabs and drives one of
+/// these per worker thread -- see its doc comment for the whole-grid vs.
+/// per-rank distinction.
+pub struct ThreadRanks {
+    rank: usize,
+    num_ranks: usize,
+    send_lo: Sender<Array3<f64>>,
+    send_hi: Sender<Array3<f64>>,
+    recv_lo: Receiver<Array3<f64>>,
+    recv_hi: Receiver<Array3<f64>>,
+    send_scalar: Sender<f64>,
+    recv_scalar: Receiver<f64>,
+}
+
+impl ThreadRanks {
+    /// Builds one `ThreadRanks` per rank in a ring of size `num_ranks`,
+    /// each already wired to its low/high z-neighbours.
+    ///
+    /// # Panics
+    /// * If `num_ranks` is zero.
+    pub fn ring(num_ranks: usize) -> Vec<ThreadRanks> {
+        assert!(num_ranks > 0, "a ring needs at least one rank");
+
+        let mut up_senders = Vec::with_capacity(num_ranks);
+        let mut up_receivers: Vec<Option<Receiver<Array3<f64>>>> = Vec::with_capacity(num_ranks);
+        let mut down_senders = Vec::with_capacity(num_ranks);
+        let mut down_receivers: Vec<Option<Receiver<Array3<f64>>>> = Vec::with_capacity(num_ranks);
+        let mut scalar_senders = Vec::with_capacity(num_ranks);
+        let mut scalar_receivers: Vec<Option<Receiver<f64>>> = Vec::with_capacity(num_ranks);
+        for _ in 0..num_ranks {
+            let (tx, rx) = mpsc::channel();
+            up_senders.push(tx);
+            up_receivers.push(Some(rx));
+            let (tx, rx) = mpsc::channel();
+            down_senders.push(tx);
+            down_receivers.push(Some(rx));
+            let (tx, rx) = mpsc::channel();
+            scalar_senders.push(tx);
+            scalar_receivers.push(Some(rx));
+        }
+
+        (0..num_ranks)
+            .map(|rank| {
+                let lo_neighbour = (rank + num_ranks - 1) % num_ranks;
+                let hi_neighbour = (rank + 1) % num_ranks;
+                ThreadRanks {
+                    rank: rank,
+                    num_ranks: num_ranks,
+                    send_hi: up_senders[rank].clone(),
+                    send_lo: down_senders[rank].clone(),
+                    recv_lo: up_receivers[lo_neighbour].take().unwrap(),
+                    recv_hi: down_receivers[hi_neighbour].take().unwrap(),
+                    send_scalar: scalar_senders[rank].clone(),
+                    recv_scalar: scalar_receivers[lo_neighbour].take().unwrap(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Transport for ThreadRanks {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn num_ranks(&self) -> usize {
+        self.num_ranks
+    }
+
+    fn exchange_halo(&self, mut phi: ArrayViewMut3<f64>) {
+        let (lo, hi) = boundary_planes(&phi.view());
+        self.send_hi.send(hi).expect("high z-neighbour rank has hung up");
+        self.send_lo.send(lo).expect("low z-neighbour rank has hung up");
+        let from_lo = self.recv_lo.recv().expect("low z-neighbour rank has hung up");
+        let from_hi = self.recv_hi.recv().expect("high z-neighbour rank has hung up");
+        fill_ghosts(&mut phi, Some(&from_lo), Some(&from_hi));
+    }
+
+    /// Single-value ring all-reduce: passes `local` around the same ring
+    /// `exchange_halo` uses until every rank has accumulated everyone else's
+    /// contribution once.
+    fn allreduce_sum(&self, local: f64) -> f64 {
+        let mut total = local;
+        let mut passing = local;
+        for _ in 1..self.num_ranks {
+            self.send_scalar.send(passing).expect("high z-neighbour rank has hung up");
+            let incoming = self.recv_scalar.recv().expect("low z-neighbour rank has hung up");
+            total += incoming;
+            passing = incoming;
+        }
+        total
+    }
+}
+
+/// Extracts the `HALO_WIDTH`-thick planes at the low and high z faces of
+/// `phi`'s work area, ready to be shipped to the neighbouring slabs by a
+/// real `Transport` impl.
+pub fn boundary_planes(phi: &ArrayView3<f64>) -> (Array3<f64>, Array3<f64>) {
+    let dims = phi.dim();
+    let lo = phi.slice(s![.., .., HALO_WIDTH..2 * HALO_WIDTH]).to_owned();
+    let hi = phi.slice(s![.., .., dims.2 - 2 * HALO_WIDTH..dims.2 - HALO_WIDTH])
+        .to_owned();
+    (lo, hi)
+}
+
+/// Writes planes received from the low/high z neighbours into `phi`'s own
+/// ghost cells, completing the halo exchange for one iteration.
+pub fn fill_ghosts(phi: &mut ArrayViewMut3<f64>, from_lo: Option<&Array3<f64>>, from_hi: Option<&Array3<f64>>) {
+    let dims = phi.dim();
+    if let Some(lo) = from_lo {
+        phi.slice_mut(s![.., .., 0..HALO_WIDTH]).assign(lo);
+    }
+    if let Some(hi) = from_hi {
+        phi.slice_mut(s![.., .., dims.2 - HALO_WIDTH..dims.2]).assign(hi);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn slab(z: usize, fill: f64) -> Array3<f64> {
+        Array3::from_elem((2, 2, z), fill)
+    }
+
+    #[test]
+    fn boundary_planes_extracts_the_halo_width_faces() {
+        let mut phi = slab(10, 0.);
+        for k in 0..10 {
+            phi.slice_mut(s![.., .., k..k + 1]).fill(k as f64);
+        }
+        let (lo, hi) = boundary_planes(&phi.view());
+        assert_eq!(lo.shape(), &[2, 2, HALO_WIDTH]);
+        assert_eq!(hi.shape(), &[2, 2, HALO_WIDTH]);
+        assert_eq!(lo[[0, 0, 0]], 3.);
+        assert_eq!(hi[[0, 0, 0]], 4.);
+    }
+
+    #[test]
+    fn fill_ghosts_writes_low_and_high_faces_only() {
+        let mut phi = slab(10, 0.);
+        let lo = slab(HALO_WIDTH, 1.);
+        let hi = slab(HALO_WIDTH, 2.);
+        fill_ghosts(&mut phi.view_mut(), Some(&lo), Some(&hi));
+        assert_eq!(phi[[0, 0, 0]], 1.);
+        assert_eq!(phi[[0, 0, 9]], 2.);
+        assert_eq!(phi[[0, 0, 5]], 0.);
+    }
+
+    #[test]
+    fn ring_wraps_low_and_high_neighbours() {
+        let ranks = ThreadRanks::ring(3);
+        assert_eq!(ranks.iter().map(Transport::rank).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(ranks.iter().all(|r| r.num_ranks() == 3));
+    }
+
+    #[test]
+    fn exchange_halo_delivers_each_slab_to_its_ring_neighbours() {
+        let ranks = ThreadRanks::ring(3);
+        let handles: Vec<_> = ranks.into_iter()
+            .map(|rank| {
+                thread::spawn(move || {
+                    let mut phi = slab(10, rank.rank() as f64);
+                    rank.exchange_halo(phi.view_mut());
+                    phi
+                })
+            })
+            .collect();
+        let results: Vec<Array3<f64>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        // Rank 1's low ghost comes from rank 0, its high ghost from rank 2.
+        assert_eq!(results[1][[0, 0, 0]], 0.);
+        assert_eq!(results[1][[0, 0, 9]], 2.);
+        // The ring wraps: rank 0's low neighbour is rank 2, rank 2's high is rank 0.
+        assert_eq!(results[0][[0, 0, 0]], 2.);
+        assert_eq!(results[2][[0, 0, 9]], 0.);
+    }
+
+    #[test]
+    fn allreduce_sum_totals_every_ranks_contribution() {
+        let ranks = ThreadRanks::ring(4);
+        let handles: Vec<_> = ranks.into_iter()
+            .map(|rank| {
+                let local = (rank.rank() + 1) as f64;
+                thread::spawn(move || rank.allreduce_sum(local))
+            })
+            .collect();
+        let totals: Vec<f64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        // 1 + 2 + 3 + 4
+        assert!(totals.iter().all(|&t| (t - 10.).abs() < 1e-12));
+    }
+
+    #[test]
+    fn allreduce_sum_is_a_no_op_with_a_single_rank() {
+        let ranks = ThreadRanks::ring(1);
+        assert_eq!(ranks[0].allreduce_sum(7.), 7.);
+    }
+}
@@ -0,0 +1,48 @@
+//! `wasm32-unknown-unknown` entry point wrapping `grid::run_in_memory` for
+//! in-browser use. Gated behind the `wasm` feature since it needs an
+//! in-memory I/O shim instead of `run`'s usual `./input` files; `grid`'s
+//! `par_apply!` macro handles the lack of a rayon thread pool on this target
+//! on its own. The `wasm` feature and its `wasm-bindgen`/`serde`/
+//! `serde_json` dependencies are declared in `Cargo.toml`, which (like
+//! `config`/`output`/`potential`) isn't part of this snapshot.
+#![cfg(feature = "wasm")]
+
+use wasm_bindgen::prelude::*;
+use config::Config;
+use grid;
+use transport::SingleRank;
+
+/// Runs a solve from a serialized `Config` (plus optional potential and
+/// wavefunction arrays) and returns the eigen-energies and flattened
+/// `Array3<f64>` results as a `JsValue`.
+///
+/// `config_js` is the `Config` struct serialized via Serde. `potential`/
+/// `wavefunctions`, when present, replace the `./input/*.csv` files `run`
+/// would otherwise look for -- there is no filesystem in the browser, so
+/// everything needed has to come in through this one call.
+#[wasm_bindgen]
+pub fn run(config_js: JsValue,
+           potential: Option<Vec<f64>>,
+           wavefunctions: Option<Vec<Vec<f64>>>)
+           -> Result<JsValue, JsValue> {
+    let config: Config = config_js.into_serde()
+        .map_err(|err| JsValue::from_str(&format!("Invalid config: {}", err)))?;
+
+    // `grid`'s `par_apply!` macro already falls back to a sequential
+    // `Zip::apply` on this target, and there is no `./input` directory to
+    // read from -- both are handled inside `grid::run_in_memory`.
+    let log = null_logger();
+    let transport = SingleRank::default();
+
+    let result = grid::run_in_memory(&config, &log, &transport, potential, wavefunctions)
+        .map_err(JsValue::from_str)?;
+
+    JsValue::from_serde(&result).map_err(|err| JsValue::from_str(&format!("Could not serialize result: {}", err)))
+}
+
+/// `slog` drain that discards everything: the browser console isn't wired
+/// up to `slog`'s terminal/json drains, and `run`'s `info!`/`warn!` calls
+/// still need somewhere to go.
+fn null_logger() -> ::slog::Logger {
+    ::slog::Logger::root(::slog::Discard, o!())
+}